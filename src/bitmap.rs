@@ -1,34 +1,510 @@
 //! Bitmap allocation in page-granularity.
 
-use bitmap_allocator::BitAlloc;
+use run_tree::{RunBitmap, RunBitmapExt};
 
 use crate::{AllocError, AllocResult, BaseAllocator, PageAllocator};
 
+/// A hierarchical free-run summary tree over a page bitmap.
+///
+/// `alloc_contiguous` on a flat bitmap has to scan for a free run, which
+/// gets expensive on the huge bitmaps needed for 64GB/1TB configs
+/// (`RunBitmap16M`/`RunBitmap256M` below). This module builds a fan-out-32
+/// tree on top of the leaf bitmap instead: each leaf is a `u32` describing
+/// 32 pages, and each level above it summarizes 32 (or, where needed to hit
+/// an exact tier capacity, a smaller power-of-two count of) children into a
+/// single [`RunSummary`] — free-page count plus the longest free run at the
+/// left edge, right edge, and anywhere in the subtree. Allocation descends
+/// from the root, pruning any subtree whose summary can't possibly satisfy
+/// the request, so a search costs roughly `O(log N)` instead of `O(N)`.
+mod run_tree {
+    use core::ops::Range;
+
+    /// Free-run statistics for a subtree of `width` pages.
+    #[derive(Clone, Copy)]
+    pub(super) struct RunSummary {
+        width: usize,
+        free: usize,
+        /// Length of the free run starting at the first page of the subtree.
+        left: usize,
+        /// Length of the free run ending at the last page of the subtree.
+        right: usize,
+        /// Length of the longest free run anywhere in the subtree.
+        best: usize,
+    }
+
+    impl RunSummary {
+        const fn empty(width: usize) -> Self {
+            Self {
+                width,
+                free: 0,
+                left: 0,
+                right: 0,
+                best: 0,
+            }
+        }
+
+        /// Combines the summaries of two adjacent subtrees, `a` followed by
+        /// `b`, into the summary of their union. This operation is
+        /// associative, so folding it left-to-right over any number of
+        /// adjacent children yields the correct summary for their parent.
+        const fn combine(a: Self, b: Self) -> Self {
+            let left = if a.free == a.width {
+                a.width + b.left
+            } else {
+                a.left
+            };
+            let right = if b.free == b.width {
+                b.width + a.right
+            } else {
+                b.right
+            };
+            let crossing = a.right + b.left;
+            let best = max3(a.best, b.best, crossing);
+            Self {
+                width: a.width + b.width,
+                free: a.free + b.free,
+                left,
+                right,
+                best,
+            }
+        }
+    }
+
+    const fn max3(a: usize, b: usize, c: usize) -> usize {
+        let ab = if a > b { a } else { b };
+        if ab > c {
+            ab
+        } else {
+            c
+        }
+    }
+
+    /// Returns the smallest value `>= abs_offset + local_lo` that is a
+    /// multiple of `align`, expressed relative to `abs_offset`, or `None`
+    /// if that value would be `> abs_offset + local_hi`.
+    fn first_aligned_local(
+        abs_offset: usize,
+        local_lo: usize,
+        local_hi: usize,
+        align: usize,
+    ) -> Option<usize> {
+        let lo = abs_offset + local_lo;
+        let aligned = lo.div_ceil(align) * align;
+        let local = aligned - abs_offset;
+        (local <= local_hi).then_some(local)
+    }
+
+    /// A node in the free-run summary tree: either a 32-page leaf word or a
+    /// `Cascade` of `N` child nodes.
+    ///
+    /// All positions (`start`, the return value of `find_run`, ...) used by
+    /// this trait are page indices relative to the node they're called on.
+    pub(super) trait RunBitmap: Copy {
+        /// Number of pages covered by this node.
+        const CAP: usize;
+        /// A node with every page marked used (not free).
+        const DEFAULT: Self;
+
+        fn summary(&self) -> RunSummary;
+        fn insert(&mut self, range: Range<usize>);
+        fn fits_free(&self, start: usize, num_pages: usize) -> bool;
+        /// Whether every page in `[start, start + num_pages)` is used (the
+        /// opposite extreme from [`fits_free`][Self::fits_free]).
+        fn fits_used(&self, start: usize, num_pages: usize) -> bool;
+        /// Searches for a free, `align`-aligned run of `num_pages` pages.
+        /// `abs_offset` is the absolute page index of this node's first
+        /// page, used only to evaluate alignment.
+        fn find_run(&self, num_pages: usize, align_log2: usize, abs_offset: usize)
+            -> Option<usize>;
+        fn mark_used(&mut self, start: usize, num_pages: usize);
+        fn mark_free(&mut self, start: usize, num_pages: usize);
+    }
+
+    /// Allocator-facing operations built generically on top of [`RunBitmap`],
+    /// mirroring the small subset of `bitmap_allocator::BitAlloc` that
+    /// [`super::BitmapPageAllocator`] relies on.
+    pub(super) trait RunBitmapExt: RunBitmap {
+        fn alloc(&mut self) -> Option<usize> {
+            let start = self.find_run(1, 0, 0)?;
+            self.mark_used(start, 1);
+            Some(start)
+        }
+
+        fn alloc_contiguous(
+            &mut self,
+            pos_hint: Option<usize>,
+            num_pages: usize,
+            align_log2: usize,
+        ) -> Option<usize> {
+            if num_pages == 0 || num_pages > Self::CAP {
+                return None;
+            }
+            let start = match pos_hint {
+                Some(idx) => {
+                    if idx + num_pages > Self::CAP || !self.fits_free(idx, num_pages) {
+                        return None;
+                    }
+                    idx
+                }
+                None => self.find_run(num_pages, align_log2, 0)?,
+            };
+            self.mark_used(start, num_pages);
+            Some(start)
+        }
+
+        /// Frees page `idx`, returning whether it was actually allocated
+        /// (as opposed to already free, e.g. on a double free).
+        fn dealloc(&mut self, idx: usize) -> bool {
+            self.dealloc_contiguous(idx, 1)
+        }
+
+        /// Frees `[start, start + num_pages)`, but only if every page in the
+        /// range was actually allocated; otherwise leaves the bitmap
+        /// unchanged and returns `false` (mirroring
+        /// `bitmap_allocator::BitAlloc::dealloc_contiguous`'s all-or-nothing
+        /// semantics, e.g. on a double free or a miscomputed range).
+        fn dealloc_contiguous(&mut self, start: usize, num_pages: usize) -> bool {
+            if !self.fits_used(start, num_pages) {
+                return false;
+            }
+            self.mark_free(start, num_pages);
+            true
+        }
+    }
+
+    impl<T: RunBitmap> RunBitmapExt for T {}
+
+    /// Returns a mask of `num_pages` consecutive free bits starting at bit
+    /// `start` of a 32-bit leaf word.
+    fn leaf_mask(start: usize, num_pages: usize) -> u32 {
+        if num_pages == 0 {
+            0
+        } else {
+            (((1u64 << num_pages) - 1) as u32) << start
+        }
+    }
+
+    /// Length of the longest run of consecutive `1` bits in `word`.
+    fn leaf_best_run(word: u32) -> usize {
+        let mut best = 0;
+        let mut run = 0;
+        for i in 0..32 {
+            if (word >> i) & 1 == 1 {
+                run += 1;
+                best = best.max(run);
+            } else {
+                run = 0;
+            }
+        }
+        best
+    }
+
+    /// A leaf word: bit `i` set means page `i` is free.
+    impl RunBitmap for u32 {
+        const CAP: usize = 32;
+        const DEFAULT: Self = 0;
+
+        fn summary(&self) -> RunSummary {
+            RunSummary {
+                width: 32,
+                free: self.count_ones() as usize,
+                left: self.trailing_ones() as usize,
+                right: self.leading_ones() as usize,
+                best: leaf_best_run(*self),
+            }
+        }
+
+        fn insert(&mut self, range: Range<usize>) {
+            *self |= leaf_mask(range.start, range.end - range.start);
+        }
+
+        fn fits_free(&self, start: usize, num_pages: usize) -> bool {
+            if start + num_pages > 32 {
+                return false;
+            }
+            let mask = leaf_mask(start, num_pages);
+            *self & mask == mask
+        }
+
+        fn fits_used(&self, start: usize, num_pages: usize) -> bool {
+            if start + num_pages > 32 {
+                return false;
+            }
+            let mask = leaf_mask(start, num_pages);
+            *self & mask == 0
+        }
+
+        fn find_run(
+            &self,
+            num_pages: usize,
+            align_log2: usize,
+            abs_offset: usize,
+        ) -> Option<usize> {
+            let align = 1usize << align_log2;
+            let mut i = 0;
+            while i + num_pages <= 32 {
+                if (abs_offset + i).is_multiple_of(align) && self.fits_free(i, num_pages) {
+                    return Some(i);
+                }
+                i += 1;
+            }
+            None
+        }
+
+        fn mark_used(&mut self, start: usize, num_pages: usize) {
+            *self &= !leaf_mask(start, num_pages);
+        }
+
+        fn mark_free(&mut self, start: usize, num_pages: usize) {
+            *self |= leaf_mask(start, num_pages);
+        }
+    }
+
+    /// An internal node fanning out into `N` children of type `T`, caching
+    /// the combined [`RunSummary`] so queries don't have to re-descend the
+    /// whole subtree.
+    #[derive(Clone, Copy)]
+    pub(super) struct Cascade<T: RunBitmap, const N: usize> {
+        children: [T; N],
+        summary: RunSummary,
+    }
+
+    impl<T: RunBitmap, const N: usize> Cascade<T, N> {
+        fn recompute_summary(&mut self) {
+            let mut acc = RunSummary::empty(0);
+            for child in &self.children {
+                acc = RunSummary::combine(acc, child.summary());
+            }
+            self.summary = acc;
+        }
+
+        /// Splits `range` (relative to `self`) across the children it
+        /// overlaps, calling `f(child, local_range)` for each.
+        fn for_each_overlap(&mut self, range: Range<usize>, mut f: impl FnMut(&mut T, Range<usize>)) {
+            for (i, child) in self.children.iter_mut().enumerate() {
+                let child_start = i * T::CAP;
+                let child_end = child_start + T::CAP;
+                let lo = range.start.max(child_start);
+                let hi = range.end.min(child_end);
+                if lo < hi {
+                    f(child, (lo - child_start)..(hi - child_start));
+                }
+            }
+        }
+    }
+
+    impl<T: RunBitmap, const N: usize> RunBitmap for Cascade<T, N> {
+        const CAP: usize = T::CAP * N;
+        const DEFAULT: Self = Self {
+            children: [T::DEFAULT; N],
+            summary: RunSummary::empty(T::CAP * N),
+        };
+
+        fn summary(&self) -> RunSummary {
+            self.summary
+        }
+
+        fn insert(&mut self, range: Range<usize>) {
+            self.for_each_overlap(range, |child, local| child.insert(local));
+            self.recompute_summary();
+        }
+
+        fn fits_free(&self, start: usize, num_pages: usize) -> bool {
+            if start + num_pages > Self::CAP {
+                return false;
+            }
+            let end = start + num_pages;
+            self.children.iter().enumerate().all(|(i, child)| {
+                let child_start = i * T::CAP;
+                let child_end = child_start + T::CAP;
+                let lo = start.max(child_start);
+                let hi = end.min(child_end);
+                lo >= hi || child.fits_free(lo - child_start, hi - lo)
+            })
+        }
+
+        fn fits_used(&self, start: usize, num_pages: usize) -> bool {
+            if start + num_pages > Self::CAP {
+                return false;
+            }
+            let end = start + num_pages;
+            self.children.iter().enumerate().all(|(i, child)| {
+                let child_start = i * T::CAP;
+                let child_end = child_start + T::CAP;
+                let lo = start.max(child_start);
+                let hi = end.min(child_end);
+                lo >= hi || child.fits_used(lo - child_start, hi - lo)
+            })
+        }
+
+        fn find_run(
+            &self,
+            num_pages: usize,
+            align_log2: usize,
+            abs_offset: usize,
+        ) -> Option<usize> {
+            if self.summary.best < num_pages {
+                return None;
+            }
+            let align = 1usize << align_log2;
+
+            // Length (and start, local to `self`) of the free run carried in
+            // from already-scanned, fully-free children, so a run can be
+            // found even when it crosses a child boundary.
+            let mut carry_len = 0usize;
+            let mut carry_start = 0usize;
+
+            for (i, child) in self.children.iter().enumerate() {
+                let child_start = i * T::CAP;
+                let child_abs = abs_offset + child_start;
+                let csum = child.summary();
+
+                if carry_len > 0 {
+                    let combined = carry_len + csum.left;
+                    if combined >= num_pages {
+                        let run_end = child_start + csum.left;
+                        let local_hi = run_end.saturating_sub(num_pages);
+                        if carry_start <= local_hi {
+                            if let Some(start) =
+                                first_aligned_local(abs_offset, carry_start, local_hi, align)
+                            {
+                                return Some(start);
+                            }
+                        }
+                    }
+                }
+
+                if csum.best >= num_pages {
+                    if let Some(local) = child.find_run(num_pages, align_log2, child_abs) {
+                        return Some(child_start + local);
+                    }
+                }
+
+                if csum.free == T::CAP {
+                    if carry_len == 0 {
+                        carry_start = child_start;
+                    }
+                    carry_len += T::CAP;
+                } else if csum.right > 0 {
+                    carry_len = csum.right;
+                    carry_start = child_start + T::CAP - csum.right;
+                } else {
+                    carry_len = 0;
+                }
+            }
+            None
+        }
+
+        fn mark_used(&mut self, start: usize, num_pages: usize) {
+            self.for_each_overlap(start..(start + num_pages), |child, local| {
+                child.mark_used(local.start, local.end - local.start)
+            });
+            self.recompute_summary();
+        }
+
+        fn mark_free(&mut self, start: usize, num_pages: usize) {
+            self.for_each_overlap(start..(start + num_pages), |child, local| {
+                child.mark_free(local.start, local.end - local.start)
+            });
+            self.recompute_summary();
+        }
+    }
+
+    // Leaf word: 32 pages.
+    type Level0 = u32;
+    // 32 * 32 = 1,024 pages.
+    type Level1 = Cascade<Level0, 32>;
+    // 32 * 1,024 = 32,768 pages.
+    type Level2 = Cascade<Level1, 32>;
+    // 32 * 32,768 = 1,048,576 pages = 4GB (assuming PAGE_SIZE = 4KB).
+    type Level3 = Cascade<Level2, 32>;
+
+    /// Max 64K pages = 256MB (assuming PAGE_SIZE = 4KB).
+    pub(super) type RunBitmap64K = Cascade<Level2, 2>;
+    /// Max 1M pages = 4GB (assuming PAGE_SIZE = 4KB).
+    pub(super) type RunBitmap1M = Level3;
+    /// Max 16M pages = 64GB (assuming PAGE_SIZE = 4KB).
+    pub(super) type RunBitmap16M = Cascade<Level3, 16>;
+    /// Max 256M pages = 1TB (assuming PAGE_SIZE = 4KB).
+    pub(super) type RunBitmap256M = Cascade<RunBitmap16M, 16>;
+}
+
 const MAX_ALIGN_1GB: usize = 0x4000_0000;
 
+/// Maximum number of discontiguous memory regions a single
+/// [`BitmapPageAllocator`] can track.
+///
+/// Bootloaders typically hand over a handful of separated RAM ranges at
+/// most, so a small fixed-size array avoids depending on a heap allocator.
+const MAX_REGIONS: usize = 4;
+
+/// Maximum number of reserved page ranges a single [`BitmapPageAllocator`]
+/// can track (kernel image, the bitmap's own storage, device-tree blob,
+/// MMIO windows, ...).
+const MAX_RESERVATIONS: usize = 8;
+
+/// A single memory range that has been added to a [`BitmapPageAllocator`],
+/// recording where it sits in the inner bitmap; used to reject addresses
+/// that fall in the gaps between regions.
+#[derive(Clone, Copy)]
+struct MemRegion {
+    base: usize,
+    start_idx: usize,
+    total_pages: usize,
+}
+
+/// A page size tier that [`BitmapPageAllocator::alloc_huge`] can hand out.
+///
+/// Each tier is naturally aligned: a `Size2M` allocation starts at a
+/// 2 MiB-aligned address, a `Size1G` one at a 1 GiB-aligned address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageSize {
+    /// A regular 4 KiB page.
+    Size4K,
+    /// A 2 MiB huge page.
+    Size2M,
+    /// A 1 GiB huge page.
+    Size1G,
+}
+
+impl PageSize {
+    /// The size of this tier, in bytes.
+    pub const fn size(self) -> usize {
+        match self {
+            Self::Size4K => 0x1000,
+            Self::Size2M => 0x20_0000,
+            Self::Size1G => MAX_ALIGN_1GB,
+        }
+    }
+}
+
+/// Number of [`PageSize`] tiers, used to size the per-tier counters.
+const PAGE_SIZE_TIERS: usize = 3;
+
 cfg_if::cfg_if! {
     if #[cfg(test)] {
         /// Use 4GB memory for testing.
-        type BitAllocUsed = bitmap_allocator::BitAlloc1M;
+        type BitAllocUsed = run_tree::RunBitmap1M;
     } else if #[cfg(feature = "page-alloc-1t")] {
         /// Support max 256M * PAGE_SIZE = 1TB memory (assume that PAGE_SIZE = 4KB).
-        type BitAllocUsed = bitmap_allocator::BitAlloc256M;
+        type BitAllocUsed = run_tree::RunBitmap256M;
     } else if #[cfg(feature = "page-alloc-64g")] {
         /// Support max 16M * PAGE_SIZE = 64GB memory (assume that PAGE_SIZE = 4KB).
-        type BitAllocUsed = bitmap_allocator::BitAlloc16M;
+        type BitAllocUsed = run_tree::RunBitmap16M;
     } else if #[cfg(feature = "page-alloc-4g")] {
         /// Support max 1M * PAGE_SIZE = 4GB memory (assume that PAGE_SIZE = 4KB).
-        type BitAllocUsed = bitmap_allocator::BitAlloc1M;
+        type BitAllocUsed = run_tree::RunBitmap1M;
     } else {// #[cfg(feature = "page-alloc-256m")]
         /// Support max 64K * PAGE_SIZE = 256MB memory (assume that PAGE_SIZE = 4KB).
-        type BitAllocUsed = bitmap_allocator::BitAlloc64K;
+        type BitAllocUsed = run_tree::RunBitmap64K;
     }
 }
 
-/// A page-granularity memory allocator based on the [bitmap_allocator].
+/// A page-granularity memory allocator based on a hierarchical bitmap.
 ///
-/// It internally uses a bitmap, each bit indicates whether a page has been
-/// allocated.
+/// It internally uses a [`run_tree`] free-run summary tree, each bit
+/// indicating whether a page has been allocated, so that contiguous
+/// allocation stays fast even on very large bitmaps.
 ///
 /// The `PAGE_SIZE` must be a power of two.
 pub struct BitmapPageAllocator<const PAGE_SIZE: usize> {
@@ -36,6 +512,12 @@ pub struct BitmapPageAllocator<const PAGE_SIZE: usize> {
     total_pages: usize,
     used_pages: usize,
     inner: BitAllocUsed,
+    regions: [Option<MemRegion>; MAX_REGIONS],
+    /// `(start_idx, num_pages)` of each range reserved via [`Self::reserve_pages`].
+    reservations: [Option<(usize, usize)>; MAX_RESERVATIONS],
+    /// Number of huge pages currently allocated via [`Self::alloc_huge`],
+    /// indexed by [`PageSize`] discriminant.
+    huge_counts: [usize; PAGE_SIZE_TIERS],
 }
 
 impl<const PAGE_SIZE: usize> BitmapPageAllocator<PAGE_SIZE> {
@@ -46,31 +528,281 @@ impl<const PAGE_SIZE: usize> BitmapPageAllocator<PAGE_SIZE> {
             total_pages: 0,
             used_pages: 0,
             inner: BitAllocUsed::DEFAULT,
+            regions: [None; MAX_REGIONS],
+            reservations: [None; MAX_RESERVATIONS],
+            huge_counts: [0; PAGE_SIZE_TIERS],
+        }
+    }
+
+    /// Returns the region (if any) that contains `addr`.
+    fn region_at(&self, addr: usize) -> Option<&MemRegion> {
+        self.regions
+            .iter()
+            .flatten()
+            .find(|r| addr >= r.base && addr < r.base + r.total_pages * PAGE_SIZE)
+    }
+
+    /// Aligns `[start, start + size)` to `PAGE_SIZE`, inserts it into the
+    /// inner bitmap as free, and records it as a new region.
+    fn insert_region(&mut self, start: usize, size: usize) -> AllocResult {
+        let end = crate::align_down(start + size, PAGE_SIZE);
+        let start = crate::align_up(start, PAGE_SIZE);
+        if end <= start {
+            return Err(AllocError::InvalidParam);
+        }
+        let total_pages = (end - start) / PAGE_SIZE;
+
+        let slot = self
+            .regions
+            .iter_mut()
+            .find(|r| r.is_none())
+            .ok_or(AllocError::NoMemory)?;
+
+        let start_idx = (start - self.base) / PAGE_SIZE;
+        self.inner.insert(start_idx..start_idx + total_pages);
+
+        *slot = Some(MemRegion {
+            base: start,
+            start_idx,
+            total_pages,
+        });
+        self.total_pages += total_pages;
+        Ok(())
+    }
+
+    /// Returns whether any page in `[idx, idx + num_pages)` has been reserved.
+    fn is_reserved(&self, idx: usize, num_pages: usize) -> bool {
+        self.reservations
+            .iter()
+            .flatten()
+            .any(|&(r_idx, r_pages)| idx < r_idx + r_pages && r_idx < idx + num_pages)
+    }
+
+    /// Marks `[base, base + num_pages * PAGE_SIZE)` as permanently used.
+    ///
+    /// Unlike pages handed out by [`alloc_pages`][PageAllocator::alloc_pages],
+    /// reserved pages are never returned to the free set: [`dealloc_pages`]
+    /// silently ignores any range that overlaps a reservation. This is meant
+    /// for carving out the kernel image, the bitmap's own backing storage,
+    /// a device-tree blob, or MMIO windows from a RAM range before it is
+    /// handed out to callers.
+    ///
+    /// Fails with [`AllocError::InvalidParam`] if `base` is not page-aligned
+    /// or does not fall within a known region, and with
+    /// [`AllocError::NoMemory`] if any page in the range is already
+    /// allocated or reserved.
+    ///
+    /// [`dealloc_pages`]: PageAllocator::dealloc_pages
+    pub fn reserve_pages(&mut self, base: usize, num_pages: usize) -> AllocResult {
+        if !crate::is_aligned(base, PAGE_SIZE) {
+            return Err(AllocError::InvalidParam);
+        }
+        if self.region_at(base).is_none() {
+            return Err(AllocError::InvalidParam);
+        }
+
+        // Check a tracking slot is available *before* touching the bitmap,
+        // so a full reservation table can't leave pages marked used without
+        // ever being recorded as reserved (which `dealloc_pages` would then
+        // be able to free, underflowing `used_pages`).
+        if self.reservations.iter().all(|r| r.is_some()) {
+            return Err(AllocError::NoMemory);
+        }
+
+        let idx = (base - self.base) / PAGE_SIZE;
+        self.inner
+            .alloc_contiguous(Some(idx), num_pages, 0)
+            .ok_or(AllocError::NoMemory)?;
+
+        let slot = self
+            .reservations
+            .iter_mut()
+            .find(|r| r.is_none())
+            .expect("checked for a free slot above");
+        *slot = Some((idx, num_pages));
+        self.used_pages += num_pages;
+        Ok(())
+    }
+
+    /// Like [`reserve_pages`][Self::reserve_pages], but takes a raw
+    /// `[start, end)` byte range instead of a page count, rounding it
+    /// outward to whole pages first.
+    ///
+    /// This is convenient for carving out a range given as symbol bounds
+    /// (e.g. `_skernel`/`_ekernel`) that aren't naturally page-aligned.
+    pub fn reserve_region(&mut self, start: usize, end: usize) -> AllocResult {
+        if end <= start {
+            return Err(AllocError::InvalidParam);
+        }
+        let aligned_start = crate::align_down(start, PAGE_SIZE);
+        let aligned_end = crate::align_up(end, PAGE_SIZE);
+        self.reserve_pages(aligned_start, (aligned_end - aligned_start) / PAGE_SIZE)
+    }
+
+    /// Binary-searches the largest contiguous, `align_log2`-aligned free run
+    /// in `[1, max_pages]` that the inner bitmap can currently satisfy,
+    /// probing (and immediately undoing) each candidate length. Returns `0`
+    /// if not even a single such page is free.
+    fn largest_feasible_run(&mut self, max_pages: usize, align_log2: usize) -> usize {
+        let mut lo = 1usize;
+        let mut hi = max_pages;
+        let mut best = 0;
+        while lo <= hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.inner.alloc_contiguous(None, mid, align_log2) {
+                Some(idx) => {
+                    self.inner.dealloc_contiguous(idx, mid);
+                    best = mid;
+                    lo = mid + 1;
+                }
+                None => hi = mid - 1,
+            }
+        }
+        best
+    }
+
+    /// Allocates the largest contiguous, `align_pow2`-aligned run of at most
+    /// `max_pages` pages that is currently available, returning its base
+    /// address together with the number of pages actually allocated.
+    ///
+    /// Unlike [`alloc_pages`][PageAllocator::alloc_pages], this never fails
+    /// with [`AllocError::NoMemory`] just because a full `max_pages` run
+    /// isn't available: it falls back to the largest run it can find, so a
+    /// caller growing a heap incrementally can keep making progress instead
+    /// of aborting. It still fails if not even a single aligned page is
+    /// free.
+    pub fn alloc_pages_partial(
+        &mut self,
+        max_pages: usize,
+        align_pow2: usize,
+    ) -> AllocResult<(usize, usize)> {
+        if align_pow2 > MAX_ALIGN_1GB || !crate::is_aligned(align_pow2, PAGE_SIZE) {
+            return Err(AllocError::InvalidParam);
+        }
+        let align_pow2 = align_pow2 / PAGE_SIZE;
+        if !align_pow2.is_power_of_two() || max_pages == 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        let align_log2 = align_pow2.trailing_zeros() as usize;
+
+        let max_pages = max_pages.min(self.available_pages());
+        if max_pages == 0 {
+            return Err(AllocError::NoMemory);
+        }
+
+        let num_pages = self.largest_feasible_run(max_pages, align_log2);
+        if num_pages == 0 {
+            return Err(AllocError::NoMemory);
+        }
+        let idx = self
+            .inner
+            .alloc_contiguous(None, num_pages, align_log2)
+            .ok_or(AllocError::NoMemory)?;
+        self.used_pages += num_pages;
+        Ok((idx * PAGE_SIZE + self.base, num_pages))
+    }
+
+    /// Allocates `count` naturally-aligned huge pages of the given
+    /// [`PageSize`] and returns the base address of the (contiguous) block.
+    ///
+    /// This is a thin wrapper around [`alloc_pages`][PageAllocator::alloc_pages]
+    /// that uses the huge page's own size as both the allocation granularity
+    /// and the alignment, and keeps a running count of how much memory is
+    /// committed as superpages of each tier; see
+    /// [`huge_pages_allocated`][Self::huge_pages_allocated]. Use
+    /// [`max_contiguous_free`][Self::max_contiguous_free] to check whether a
+    /// large enough run exists before calling this.
+    pub fn alloc_huge(&mut self, size: PageSize, count: usize) -> AllocResult<usize> {
+        if count == 0 || !crate::is_aligned(size.size(), PAGE_SIZE) {
+            return Err(AllocError::InvalidParam);
+        }
+        let pages_per_huge = size.size() / PAGE_SIZE;
+        let num_pages = pages_per_huge
+            .checked_mul(count)
+            .ok_or(AllocError::InvalidParam)?;
+
+        let addr = self.alloc_pages(num_pages, size.size())?;
+        self.huge_counts[size as usize] += count;
+        Ok(addr)
+    }
+
+    /// Frees `count` huge pages of the given [`PageSize`] previously
+    /// returned by [`alloc_huge`][Self::alloc_huge], starting at `pos`.
+    pub fn dealloc_huge(&mut self, pos: usize, size: PageSize, count: usize) {
+        let pages_per_huge = size.size() / PAGE_SIZE;
+        if self.dealloc_pages_impl(pos, pages_per_huge * count) {
+            self.huge_counts[size as usize] =
+                self.huge_counts[size as usize].saturating_sub(count);
         }
     }
+
+    /// Frees `[pos, pos + num_pages * PAGE_SIZE)`, returning whether the
+    /// range was actually transitioned from used to free, as opposed to a
+    /// double free or a range overlapping a reservation — both of which are
+    /// silently ignored. Counters that should only move in lockstep with a
+    /// real free (like the per-tier count in
+    /// [`dealloc_huge`][Self::dealloc_huge]) must gate on this return value
+    /// rather than decrementing unconditionally.
+    fn dealloc_pages_impl(&mut self, pos: usize, num_pages: usize) -> bool {
+        assert!(
+            crate::is_aligned(pos, PAGE_SIZE),
+            "pos must be aligned to PAGE_SIZE"
+        );
+
+        // Reserved pages were never really "allocated" from the caller's
+        // point of view, so a free of (part of) a reserved range must not
+        // resurrect it.
+        let idx = (pos - self.base) / PAGE_SIZE;
+        if self.is_reserved(idx, num_pages) {
+            return false;
+        }
+
+        let freed = match num_pages.cmp(&1) {
+            core::cmp::Ordering::Equal => self.inner.dealloc(idx),
+            core::cmp::Ordering::Greater => self.inner.dealloc_contiguous(idx, num_pages),
+            _ => false,
+        };
+        if freed {
+            self.used_pages -= num_pages;
+        }
+        freed
+    }
+
+    /// Returns how many huge pages of the given [`PageSize`] are currently
+    /// allocated via [`alloc_huge`][Self::alloc_huge].
+    pub fn huge_pages_allocated(&self, size: PageSize) -> usize {
+        self.huge_counts[size as usize]
+    }
+
+    /// Returns the length, in pages, of the largest contiguous free run
+    /// currently available, at any alignment.
+    ///
+    /// Useful for deciding whether a [`PageSize::Size2M`] or
+    /// [`PageSize::Size1G`] superpage mapping is even possible before
+    /// attempting [`alloc_huge`][Self::alloc_huge].
+    pub fn max_contiguous_free(&mut self) -> usize {
+        self.largest_feasible_run(self.available_pages(), 0)
+    }
 }
 
 impl<const PAGE_SIZE: usize> BaseAllocator for BitmapPageAllocator<PAGE_SIZE> {
     fn init(&mut self, start: usize, size: usize) {
         assert!(PAGE_SIZE.is_power_of_two());
 
-        // Range for real:  [align_up(start, PAGE_SIZE), align_down(start + size, PAGE_SIZE))
-        let end = crate::align_down(start + size, PAGE_SIZE);
-        let start = crate::align_up(start, PAGE_SIZE);
-        self.total_pages = (end - start) / PAGE_SIZE;
-
         // Calculate the base offset stored in the real [`BitAlloc`] instance.
-        self.base = crate::align_down(start, MAX_ALIGN_1GB);
+        // All regions, including the ones added later via `add_memory`, are
+        // indexed relative to this single base.
+        self.base = crate::align_down(crate::align_up(start, PAGE_SIZE), MAX_ALIGN_1GB);
 
-        // Range in bitmap: [start - self.base, start - self.base + total_pages * PAGE_SIZE)
-        let start = start - self.base;
-        let start_idx = start / PAGE_SIZE;
-
-        self.inner.insert(start_idx..start_idx + self.total_pages);
+        // A `start`/`size` that rounds down to an empty range (e.g. a
+        // sub-page-sized or misaligned fragment handed over by a
+        // bootloader) is ignored: no region is recorded and `total_pages`
+        // stays at 0, rather than panicking on caller-supplied size.
+        let _ = self.insert_region(start, size);
     }
 
-    fn add_memory(&mut self, _start: usize, _size: usize) -> AllocResult {
-        Err(AllocError::NoMemory) // unsupported
+    fn add_memory(&mut self, start: usize, size: usize) -> AllocResult {
+        self.insert_region(start, size)
     }
 }
 
@@ -124,6 +856,13 @@ impl<const PAGE_SIZE: usize> PageAllocator for BitmapPageAllocator<PAGE_SIZE> {
         }
         let align_log2 = align_pow2.trailing_zeros() as usize;
 
+        // `base` must fall inside a region that was actually handed to us;
+        // otherwise it either lies in an unmanaged gap between regions or
+        // was never added at all.
+        if self.region_at(base).is_none() {
+            return Err(AllocError::InvalidParam);
+        }
+
         let idx = (base - self.base) / PAGE_SIZE;
 
         self.inner
@@ -134,19 +873,7 @@ impl<const PAGE_SIZE: usize> PageAllocator for BitmapPageAllocator<PAGE_SIZE> {
     }
 
     fn dealloc_pages(&mut self, pos: usize, num_pages: usize) {
-        assert!(
-            crate::is_aligned(pos, Self::PAGE_SIZE),
-            "pos must be aligned to PAGE_SIZE"
-        );
-        if match num_pages.cmp(&1) {
-            core::cmp::Ordering::Equal => self.inner.dealloc((pos - self.base) / PAGE_SIZE),
-            core::cmp::Ordering::Greater => self
-                .inner
-                .dealloc_contiguous((pos - self.base) / PAGE_SIZE, num_pages),
-            _ => false,
-        } {
-            self.used_pages -= num_pages;
-        }
+        self.dealloc_pages_impl(pos, num_pages);
     }
 
     fn total_pages(&self) -> usize {
@@ -328,4 +1055,177 @@ mod tests {
             i += 1;
         }
     }
+
+    #[test]
+    fn test_bitmap_page_allocator_discontiguous_regions() {
+        const SIZE_1G: usize = 1024 * 1024 * 1024;
+
+        let mut allocator = BitmapPageAllocator::<PAGE_SIZE>::new();
+        allocator.init(0, SIZE_1G);
+        assert_eq!(allocator.total_pages(), SIZE_1G / PAGE_SIZE);
+
+        // Add a second, disjoint region far away from the first one (but
+        // still within the 4GB address space the `#[cfg(test)]` bitmap
+        // tier can track).
+        const REGION2_BASE: usize = 2 * SIZE_1G;
+        allocator.add_memory(REGION2_BASE, SIZE_1G).unwrap();
+        assert_eq!(allocator.total_pages(), 2 * SIZE_1G / PAGE_SIZE);
+        assert_eq!(allocator.used_pages(), 0);
+        assert_eq!(allocator.available_pages(), 2 * SIZE_1G / PAGE_SIZE);
+
+        // Allocating at a specific address in the second region should work.
+        let addr = allocator
+            .alloc_pages_at(REGION2_BASE, 10, PAGE_SIZE)
+            .unwrap();
+        assert_eq!(addr, REGION2_BASE);
+        assert_eq!(allocator.used_pages(), 10);
+
+        // An address that falls in the gap between the two regions is not
+        // backed by either of them and must be rejected.
+        assert!(allocator
+            .alloc_pages_at(SIZE_1G + PAGE_SIZE, 1, PAGE_SIZE)
+            .is_err());
+
+        allocator.dealloc_pages(addr, 10);
+        assert_eq!(allocator.used_pages(), 0);
+
+        // A normal (region-agnostic) allocation should still be able to use
+        // free space from either region.
+        let addr = allocator.alloc_pages(SIZE_1G / PAGE_SIZE, PAGE_SIZE).unwrap();
+        assert_eq!(addr, 0);
+        assert_eq!(allocator.used_pages(), SIZE_1G / PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_bitmap_page_allocator_reserve_pages() {
+        const SIZE_1M: usize = 1024 * 1024;
+
+        let mut allocator = BitmapPageAllocator::<PAGE_SIZE>::new();
+        allocator.init(0, SIZE_1M);
+
+        // Carve out the first 16 pages, e.g. for a kernel image.
+        allocator.reserve_pages(0, 16).unwrap();
+        assert_eq!(allocator.used_pages(), 16);
+        assert_eq!(allocator.available_pages(), SIZE_1M / PAGE_SIZE - 16);
+
+        // Reserving an overlapping range must fail and not double-count.
+        assert!(allocator.reserve_pages(8 * PAGE_SIZE, 4).is_err());
+        assert_eq!(allocator.used_pages(), 16);
+
+        // A normal allocation must not be able to hand out reserved pages.
+        let addr = allocator.alloc_pages(16, PAGE_SIZE).unwrap();
+        assert_eq!(addr, 16 * PAGE_SIZE);
+
+        // Freeing the reserved range must be a no-op: the pages stay used.
+        allocator.dealloc_pages(0, 16);
+        assert_eq!(allocator.used_pages(), 32);
+        assert_eq!(allocator.available_pages(), SIZE_1M / PAGE_SIZE - 32);
+
+        // A raw, non-page-aligned range gets rounded outward.
+        allocator.dealloc_pages(addr, 16);
+        allocator
+            .reserve_region(20 * PAGE_SIZE + 1, 21 * PAGE_SIZE - 1)
+            .unwrap();
+        assert_eq!(allocator.used_pages(), 17);
+    }
+
+    #[test]
+    fn test_bitmap_page_allocator_alloc_pages_partial() {
+        const SIZE_1M: usize = 1024 * 1024;
+        let total_pages = SIZE_1M / PAGE_SIZE;
+
+        let mut allocator = BitmapPageAllocator::<PAGE_SIZE>::new();
+        allocator.init(0, SIZE_1M);
+
+        // Plenty of room: a full request is satisfied in one go.
+        let (addr, num_pages) = allocator.alloc_pages_partial(10, PAGE_SIZE).unwrap();
+        assert_eq!(addr, 0);
+        assert_eq!(num_pages, 10);
+        assert_eq!(allocator.used_pages(), 10);
+
+        // Eat up all but the last 5 pages, then ask for more than remains:
+        // we should get back exactly what's left instead of an error.
+        allocator
+            .alloc_pages(total_pages - 10 - 5, PAGE_SIZE)
+            .unwrap();
+        assert_eq!(allocator.available_pages(), 5);
+
+        let (_addr, num_pages) = allocator.alloc_pages_partial(100, PAGE_SIZE).unwrap();
+        assert_eq!(num_pages, 5);
+        assert_eq!(allocator.available_pages(), 0);
+
+        // Nothing left at all.
+        assert!(allocator.alloc_pages_partial(1, PAGE_SIZE).is_err());
+    }
+
+    #[test]
+    fn test_bitmap_page_allocator_alloc_huge() {
+        const SIZE_1G: usize = 1024 * 1024 * 1024;
+        const SIZE_4M: usize = 4 * 1024 * 1024;
+
+        let mut allocator = BitmapPageAllocator::<PAGE_SIZE>::new();
+        allocator.init(0, SIZE_1G);
+
+        assert_eq!(
+            allocator.max_contiguous_free(),
+            SIZE_1G / PAGE_SIZE,
+            "a pristine region should be one giant free run"
+        );
+
+        let addr = allocator.alloc_huge(PageSize::Size2M, 2).unwrap();
+        assert!(crate::is_aligned(addr, PageSize::Size2M.size()));
+        assert_eq!(allocator.used_pages(), SIZE_4M / PAGE_SIZE);
+        assert_eq!(allocator.huge_pages_allocated(PageSize::Size2M), 2);
+        assert_eq!(allocator.huge_pages_allocated(PageSize::Size1G), 0);
+
+        allocator.dealloc_huge(addr, PageSize::Size2M, 2);
+        assert_eq!(allocator.used_pages(), 0);
+        assert_eq!(allocator.huge_pages_allocated(PageSize::Size2M), 0);
+
+        // A single 1G-aligned 1G huge page fits in exactly a 1G region.
+        let addr = allocator.alloc_huge(PageSize::Size1G, 1).unwrap();
+        assert_eq!(addr, 0);
+        assert_eq!(allocator.huge_pages_allocated(PageSize::Size1G), 1);
+
+        // No room for a second one.
+        assert!(allocator.alloc_huge(PageSize::Size1G, 1).is_err());
+    }
+
+    #[test]
+    fn test_bitmap_page_allocator_run_crosses_boundaries() {
+        const SIZE_8M: usize = 8 * 1024 * 1024;
+        let total_pages = SIZE_8M / PAGE_SIZE;
+
+        let mut allocator = BitmapPageAllocator::<PAGE_SIZE>::new();
+        allocator.init(0, SIZE_8M);
+
+        // Carve out every 1,024th page (a `Level1` cascade-node boundary in
+        // the summary tree, i.e. 32 leaf words) so that every large free
+        // run has to be stitched together from many small ones crossing
+        // leaf-word boundaries. The reserved page sits on each `Level1`
+        // boundary, so no free run crosses one of those.
+        let mut reserved = 0;
+        let mut pos = 0;
+        while pos < total_pages {
+            allocator.reserve_pages(pos * PAGE_SIZE, 1).unwrap();
+            reserved += 1;
+            pos += 1024;
+        }
+
+        assert_eq!(allocator.used_pages(), reserved);
+        assert_eq!(
+            allocator.max_contiguous_free(),
+            1023,
+            "the largest free run should be bounded by the nearest reserved page"
+        );
+
+        // A run of 1023 pages must fit exactly between two reserved pages.
+        let addr = allocator.alloc_pages(1023, PAGE_SIZE).unwrap();
+        assert_eq!(addr, PAGE_SIZE);
+        assert_eq!(allocator.used_pages(), reserved + 1023);
+
+        // One more page than that can't fit anywhere.
+        allocator.dealloc_pages(addr, 1023);
+        assert!(allocator.alloc_pages(1024, PAGE_SIZE).is_err());
+    }
 }